@@ -1,4 +1,5 @@
 use bytes::Bytes;
+use std::collections::HashMap;
 use std::fmt::Write;
 use std::path::{Path, PathBuf};
 use std::time::Duration;
@@ -13,10 +14,12 @@ use indicatif::ProgressBar;
 use indicatif::ProgressState;
 use indicatif::ProgressStyle;
 use librespot::core::session::Session;
+use librespot::playback::config::Bitrate;
 use librespot::playback::config::PlayerConfig;
 use librespot::playback::mixer::NoOpVolume;
 use librespot::playback::mixer::VolumeGetter;
 use librespot::playback::player::Player;
+use lofty::{Accessor, AudioFile, Probe, TaggedFileExt};
 
 use crate::channel_sink::ChannelSink;
 use crate::encoder::Format;
@@ -25,6 +28,13 @@ use crate::channel_sink::SinkEvent;
 use crate::track::Track;
 use crate::track::TrackMetadata;
 
+/// The sample rate librespot hands us decoded audio at.
+const SOURCE_SAMPLERATE: u32 = 44100;
+
+/// Lowest `max_samplerate` we'll accept; anything below this can't carry
+/// recognizable music and almost certainly means the value was mistyped.
+const MIN_SAMPLERATE: u32 = 8000;
+
 pub struct Downloader {
     player_config: PlayerConfig,
     session: Session,
@@ -37,7 +47,11 @@ pub struct DownloadOptions {
     pub compression: Option<u32>,
     pub parallel: usize,
     pub format: Format,
-    pub folder_structure: FolderStructure
+    pub quality: QualityPreset,
+    pub max_samplerate: Option<u32>,
+    pub dedup: Option<MusicSimilarity>,
+    pub normalize: bool,
+    pub template: String,
 }
 
 impl DownloadOptions {
@@ -46,16 +60,111 @@ impl DownloadOptions {
         compression: Option<u32>,
         parallel: usize,
         format: Format,
-        folder_structure: FolderStructure
-    ) -> Self {
+        folder_structure: FolderStructure,
+        quality: QualityPreset,
+        max_samplerate: Option<u32>,
+        dedup: Option<MusicSimilarity>,
+        normalize: bool,
+        template: Option<String>,
+    ) -> Result<Self> {
+        if let Some(rate) = max_samplerate {
+            if rate < MIN_SAMPLERATE {
+                return Err(anyhow::anyhow!(
+                    "max_samplerate must be at least {}Hz, got {}Hz", MIN_SAMPLERATE, rate
+                ));
+            }
+        }
+
+        // YEAR/DURATION only refine a TITLE/ARTIST/ALBUM_TITLE match; without
+        // at least one of those, every file on disk would collapse into the
+        // same bucket and dedup would treat unrelated tracks as duplicates.
+        // Reject rather than silently strengthening the caller's flags: a
+        // YEAR-only dedup might be exactly the (looser) tradeoff they want.
+        if let Some(flags) = dedup {
+            let identity_flags = MusicSimilarity::TITLE | MusicSimilarity::ARTIST | MusicSimilarity::ALBUM_TITLE;
+            if !flags.intersects(identity_flags) {
+                return Err(anyhow::anyhow!(
+                    "dedup flags must include at least one of TITLE, ARTIST, or ALBUM_TITLE, got {:?}", flags
+                ));
+            }
+        }
+
         let destination =
             destination.map_or_else(|| std::env::current_dir().unwrap(), PathBuf::from);
-        DownloadOptions {
+        let template = template.unwrap_or_else(|| folder_structure.default_template().to_string());
+        Ok(DownloadOptions {
             destination,
             compression,
             parallel,
             format,
-            folder_structure
+            quality,
+            max_samplerate,
+            dedup,
+            normalize,
+            template,
+        })
+    }
+}
+
+bitflags::bitflags! {
+    /// Which tag fields must match for a file to count as a duplicate.
+    #[derive(Debug, Clone, Copy, PartialEq, Eq)]
+    pub struct MusicSimilarity: u8 {
+        const TITLE = 1 << 0;
+        const ARTIST = 1 << 1;
+        const ALBUM_TITLE = 1 << 2;
+        const YEAR = 1 << 3;
+        const DURATION = 1 << 4;
+    }
+}
+
+/// ReplayGain-style loudness data for a track, written into the encoded
+/// file's tags (`REPLAYGAIN_TRACK_GAIN`/`REPLAYGAIN_ALBUM_GAIN` and their
+/// peak counterparts) instead of being baked into the audio itself.
+#[derive(Debug, Clone, Copy)]
+pub struct ReplayGain {
+    pub track_gain_db: f32,
+    pub track_peak: f32,
+    pub album_gain_db: f32,
+    pub album_peak: f32,
+}
+
+/// Source bitrate ceiling to request from Spotify before encoding.
+///
+/// `librespot` has no way to force a specific container format (Ogg vs.
+/// MP3) — Spotify's catalog picks that server-side, and a standard client
+/// effectively never sees `MP3_*` renditions at all. The bitrate ceiling is
+/// the only real knob available here, so that's all these presets express.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum QualityPreset {
+    /// Caps the source at roughly 96kbps.
+    Low,
+    /// Caps the source at roughly 160kbps.
+    Medium,
+    /// Requests the best bitrate available, with no ceiling.
+    BestBitrate,
+}
+
+impl QualityPreset {
+    /// The `librespot` bitrate ceiling for this preset.
+    fn bitrate(&self) -> Bitrate {
+        match self {
+            QualityPreset::Low => Bitrate::Bitrate96,
+            QualityPreset::Medium => Bitrate::Bitrate160,
+            QualityPreset::BestBitrate => Bitrate::Bitrate320,
+        }
+    }
+}
+
+impl FromStr for QualityPreset {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> std::result::Result<Self, <Self as FromStr>::Err> {
+        match s.to_uppercase().as_str() {
+            "LOW" => Ok(Self::Low),
+            "MEDIUM" => Ok(Self::Medium),
+            "BEST_BITRATE" => Ok(Self::BestBitrate),
+            _ => Err(anyhow::anyhow!("Unrecognized quality preset: {}", s))
         }
     }
 }
@@ -78,6 +187,18 @@ impl FromStr for FolderStructure {
     }
 }
 
+impl FolderStructure {
+    /// The template this structure expands to when `DownloadOptions.template`
+    /// isn't set explicitly, kept for backward compatibility with the
+    /// original FLAT/ALBUM layouts.
+    fn default_template(&self) -> &'static str {
+        match self {
+            FolderStructure::FLAT => "{artist} - {title}",
+            FolderStructure::ALBUM => "{album_artist}/{album_disc}/{track:02} {title}",
+        }
+    }
+}
+
 impl Downloader {
     pub fn new(session: Session) -> Self {
         Downloader {
@@ -92,9 +213,11 @@ impl Downloader {
         tracks: Vec<Track>,
         options: &DownloadOptions,
     ) -> Result<()> {
+        let library = self.scan_library(options)?;
+
         futures::stream::iter(tracks)
             .map(|track| {
-                self.download_track(track, options)
+                self.download_track(track, options, &library)
             })
             .buffer_unordered(options.parallel)
             .try_collect::<Vec<_>>()
@@ -103,8 +226,8 @@ impl Downloader {
         Ok(())
     }
 
-    #[tracing::instrument(name = "download_track", skip(self))]
-    async fn download_track(&self, track: Track, options: &DownloadOptions) -> Result<()> {
+    #[tracing::instrument(name = "download_track", skip(self, library))]
+    async fn download_track(&self, track: Track, options: &DownloadOptions, library: &LibraryIndex) -> Result<()> {
         let pb = self.progress_bar.add(ProgressBar::new(1));
         pb.set_style(ProgressStyle::with_template("{spinner:.green} {msg} [{elapsed_precise}] [{wide_bar:.cyan/blue}] {bytes}/{total_bytes} ({eta})")?
             .with_key("eta", |state: &ProgressState, w: &mut dyn Write| write!(w, "{:.1}s", state.eta().as_secs_f64()).unwrap())
@@ -133,7 +256,17 @@ impl Downloader {
         let metadata = track.metadata(&self.session).await?;
         tracing::info!("Downloading track: {:?}", metadata);
 
-        let file_name = self.get_file_name(&metadata, FolderStructure::ALBUM);
+        if let Some(flags) = options.dedup {
+            if let Some(existing) = library.find(&metadata, flags) {
+                tracing::info!("Track already exists in library, skipping: {:?}", existing);
+                pb.set_message(format!("{}", existing.display()));
+                write_link(existing, &link_path)?;
+                pb.finish_with_message(format!("Already in library: {}", existing.display()));
+                return Ok(());
+            }
+        }
+
+        let file_name = self.get_file_name(&metadata, &options.template);
         let path = options
             .destination
             .join(file_name.clone())
@@ -150,7 +283,7 @@ impl Downloader {
         pb.set_length(file_size as u64);
 
         let player = Player::new(
-            self.player_config.clone(),
+            self.player_config(options),
             self.session.clone(),
             self.volume_getter(),
             move || Box::new(sink),
@@ -183,11 +316,25 @@ impl Downloader {
             }
         }
 
+        let target_samplerate = options
+            .max_samplerate
+            .filter(|&rate| rate < SOURCE_SAMPLERATE)
+            .unwrap_or(SOURCE_SAMPLERATE);
+
+        let samples = if target_samplerate != SOURCE_SAMPLERATE {
+            tracing::info!("Resampling track: {:?} from {}Hz to {}Hz", file_name, SOURCE_SAMPLERATE, target_samplerate);
+            resample_stereo(&samples, SOURCE_SAMPLERATE, target_samplerate)
+        } else {
+            samples
+        };
+
+        let replaygain = self.replaygain(options, &metadata);
+
         tracing::info!("Encoding track: {:?}", file_name);
         pb.set_message(format!("Encoding {}", &file_name));
-        let samples = Samples::new(samples, 44100, 2, 16);
+        let samples = Samples::new(samples, target_samplerate, 2, 16);
         let encoder = crate::encoder::get_encoder(options.format);
-        let stream = encoder.encode(samples, metadata, album_art).await?;
+        let stream = encoder.encode(samples, metadata, album_art, replaygain).await?;
 
         pb.set_message(format!("Writing {}", &file_name));
         tracing::info!("Writing track: {:?} to file: {}", file_name, &path);
@@ -203,50 +350,41 @@ impl Downloader {
         Box::new(NoOpVolume)
     }
 
-    fn get_file_name(&self, metadata: &TrackMetadata, structure: FolderStructure) -> String {
-        // If there is more than 3 artists, add the first 3 and add "and others" at the end
-        let artists = metadata
-                .artists
-                .iter()
-            .map(|artist| artist.name.clone());
+    fn player_config(&self, options: &DownloadOptions) -> PlayerConfig {
+        PlayerConfig {
+            bitrate: options.quality.bitrate(),
+            // Left at the inherited default (disabled), not tied to
+            // `options.normalize`. `metadata.normalisation` already carries
+            // the stream's gain data independent of this flag, and setting
+            // it here would make librespot bake the same gain into the
+            // decoded PCM that we also write out as `REPLAYGAIN_*` tags,
+            // so a ReplayGain-aware player would double-apply it.
+            ..self.player_config.clone()
+        }
+    }
 
-        let artists_name = if artists.len() > 3 {
-            artists
-                .take(3)
-                .chain(["and others".to_string()])
-                .collect::<Vec<_>>()
-                .join(", ")
-        } else {
-            artists.collect::<Vec<String>>().join(", ")
-        };
+    /// Track/album gain and peak, as `librespot` parses them from the
+    /// Spotify-hosted audio stream. `None` when normalization wasn't
+    /// requested or the stream didn't carry normalization data.
+    fn replaygain(&self, options: &DownloadOptions, metadata: &TrackMetadata) -> Option<ReplayGain> {
+        if !options.normalize {
+            return None;
+        }
 
-        let album_artist = metadata
-            .artists
-            .iter()
-            .take(1)
-            .map(|artist| artist.name.clone())
-            .collect::<Vec<String>>()
-            .join(", ");
-
-
-        let parts = match structure {
-            FolderStructure::FLAT => vec![
-                format!("{} - {}", artists_name, metadata.track_name)
-            ],
-            FolderStructure::ALBUM => vec![
-                album_artist,
-                match metadata.album.num_discs {
-                    1 => metadata.album.name.clone(),
-                    _ => format!("{} (Disc {})", metadata.album.name, metadata.disc_number)
-                },
-                format!("{:0>2} {}", metadata.number, metadata.track_name)
-            ]
-        };
+        metadata.normalisation.map(|data| ReplayGain {
+            track_gain_db: data.track_gain_db,
+            track_peak: data.track_peak,
+            album_gain_db: data.album_gain_db,
+            album_peak: data.album_peak,
+        })
+    }
 
-        parts.into_iter()
-            .map(|part|  self.clean_file_name(part))
-            .collect::<Vec<_>>()
-            .join("/")
+    fn get_file_name(&self, metadata: &TrackMetadata, template: &str) -> String {
+        render_template(
+            template,
+            |token| render_placeholder(token, metadata),
+            |value| self.clean_file_name(value),
+        )
     }
 
     fn clean_file_name(&self, file_name: String) -> String {
@@ -275,6 +413,287 @@ impl Downloader {
             None => Err(anyhow::anyhow!("No cover art!"))
         }
     }
+
+    /// Builds a dedup index from tags already on disk. Empty when
+    /// `options.dedup` is `None`.
+    fn scan_library(&self, options: &DownloadOptions) -> Result<LibraryIndex> {
+        let Some(flags) = options.dedup else {
+            return Ok(LibraryIndex::new(MusicSimilarity::empty()));
+        };
+
+        let mut entries = Vec::new();
+        if options.destination.exists() {
+            self.scan_library_dir(&options.destination, &mut entries, flags)?;
+        }
+
+        tracing::info!("Library scan found {} existing tracks", entries.len());
+        Ok(LibraryIndex::new(flags).with_entries(entries))
+    }
+
+    fn scan_library_dir(&self, dir: &Path, entries: &mut Vec<LibraryEntry>, flags: MusicSimilarity) -> Result<()> {
+        for entry in fs::read_dir(dir)? {
+            let entry = entry?;
+            let path = entry.path();
+            let file_type = entry.file_type()?;
+
+            if file_type.is_symlink() {
+                continue;
+            }
+
+            if file_type.is_dir() {
+                if path.file_name().is_some_and(|name| name == ".index") {
+                    continue;
+                }
+                self.scan_library_dir(&path, entries, flags)?;
+                continue;
+            }
+
+            if let Some(entry) = LibraryEntry::read(&path, flags) {
+                entries.push(entry);
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Downsamples an interleaved stereo `i32` buffer from `src_rate` to
+/// `dst_rate` using per-channel linear interpolation.
+///
+/// For each output index `i`, the fractional source position `i * src_rate /
+/// dst_rate` is computed, and the two neighboring source samples are
+/// interpolated. The last source index is clamped so the tail of the buffer
+/// never reads out of bounds.
+fn resample_stereo(samples: &[i32], src_rate: u32, dst_rate: u32) -> Vec<i32> {
+    const CHANNELS: usize = 2;
+
+    let left: Vec<i32> = samples.iter().copied().step_by(CHANNELS).collect();
+    let right: Vec<i32> = samples.iter().copied().skip(1).step_by(CHANNELS).collect();
+    let src_len = left.len();
+    if src_len == 0 {
+        return Vec::new();
+    }
+
+    let dst_len = (src_len as u64 * dst_rate as u64 / src_rate as u64) as usize;
+    let mut out = Vec::with_capacity(dst_len * CHANNELS);
+
+    for i in 0..dst_len {
+        let pos = i as f64 * src_rate as f64 / dst_rate as f64;
+        let idx0 = (pos.floor() as usize).min(src_len - 1);
+        let idx1 = (idx0 + 1).min(src_len - 1);
+        let frac = pos - idx0 as f64;
+
+        for channel in [&left, &right] {
+            let s0 = channel[idx0] as f64;
+            let s1 = channel[idx1] as f64;
+            out.push((s0 + (s1 - s0) * frac).round() as i32);
+        }
+    }
+
+    out
+}
+
+/// In-memory index of tracks already present on disk.
+struct LibraryIndex {
+    flags: MusicSimilarity,
+    by_key: HashMap<(String, String, String), Vec<LibraryEntry>>,
+}
+
+impl LibraryIndex {
+    fn new(flags: MusicSimilarity) -> Self {
+        LibraryIndex { flags, by_key: HashMap::new() }
+    }
+
+    fn with_entries(mut self, entries: Vec<LibraryEntry>) -> Self {
+        for entry in entries {
+            let key = entry.key(self.flags);
+            self.by_key.entry(key).or_default().push(entry);
+        }
+        self
+    }
+
+    /// Path of an existing file matching `metadata` under `flags`, if any.
+    fn find(&self, metadata: &TrackMetadata, flags: MusicSimilarity) -> Option<&Path> {
+        if self.flags.is_empty() {
+            return None;
+        }
+
+        let key = LibraryEntry::metadata_key(metadata, self.flags);
+        self.by_key.get(&key)?.iter().find(|entry| entry.matches(metadata, flags)).map(|entry| entry.path.as_path())
+    }
+}
+
+/// Tags read off one file already present in the library.
+struct LibraryEntry {
+    title: String,
+    artist: String,
+    album: String,
+    year: Option<i32>,
+    duration_ms: Option<u32>,
+    path: PathBuf,
+}
+
+impl LibraryEntry {
+    /// Only reads the tag fields `flags` actually needs, so a file missing
+    /// an unrelated tag (e.g. no album tag, when only TITLE|ARTIST were
+    /// requested) still gets indexed.
+    fn read(path: &Path, flags: MusicSimilarity) -> Option<Self> {
+        let tagged_file = Probe::open(path).ok()?.read().ok()?;
+        let tag = tagged_file.primary_tag().or_else(|| tagged_file.first_tag())?;
+
+        let title = if flags.contains(MusicSimilarity::TITLE) {
+            normalize(tag.title()?.as_ref())
+        } else {
+            String::new()
+        };
+        let artist = if flags.contains(MusicSimilarity::ARTIST) {
+            normalize(tag.artist()?.as_ref())
+        } else {
+            String::new()
+        };
+        let album = if flags.contains(MusicSimilarity::ALBUM_TITLE) {
+            normalize(tag.album()?.as_ref())
+        } else {
+            String::new()
+        };
+
+        Some(LibraryEntry {
+            title,
+            artist,
+            album,
+            year: tag.year().map(|year| year as i32),
+            duration_ms: Some(tagged_file.properties().duration().as_millis() as u32),
+            path: path.to_path_buf(),
+        })
+    }
+
+    fn key(&self, flags: MusicSimilarity) -> (String, String, String) {
+        (
+            if flags.contains(MusicSimilarity::TITLE) { self.title.clone() } else { String::new() },
+            if flags.contains(MusicSimilarity::ARTIST) { self.artist.clone() } else { String::new() },
+            if flags.contains(MusicSimilarity::ALBUM_TITLE) { self.album.clone() } else { String::new() },
+        )
+    }
+
+    fn metadata_key(metadata: &TrackMetadata, flags: MusicSimilarity) -> (String, String, String) {
+        let artist = metadata.artists.first().map(|artist| artist.name.as_str()).unwrap_or_default();
+        (
+            if flags.contains(MusicSimilarity::TITLE) { normalize(&metadata.track_name) } else { String::new() },
+            if flags.contains(MusicSimilarity::ARTIST) { normalize(artist) } else { String::new() },
+            if flags.contains(MusicSimilarity::ALBUM_TITLE) { normalize(&metadata.album.name) } else { String::new() },
+        )
+    }
+
+    fn matches(&self, metadata: &TrackMetadata, flags: MusicSimilarity) -> bool {
+        if flags.contains(MusicSimilarity::YEAR) && self.year != metadata.album.year.map(|year| year as i32) {
+            return false;
+        }
+
+        if flags.contains(MusicSimilarity::DURATION) && self.duration_ms != metadata.duration_ms {
+            return false;
+        }
+
+        true
+    }
+}
+
+/// Lowercases and strips punctuation for fuzzy tag comparison.
+fn normalize(text: &str) -> String {
+    text.to_lowercase()
+        .chars()
+        .filter(|c| c.is_alphanumeric() || c.is_whitespace())
+        .collect::<String>()
+        .split_whitespace()
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+/// Renders a filename/path template by substituting `{placeholder}` tokens,
+/// resolving each one via `resolve` and passing the resolved value through
+/// `clean` before splicing it into the output. The template's own `/`
+/// separators are never passed to `clean`, so a resolved value containing a
+/// literal `/` (e.g. an artist named "AC/DC") gets stripped instead of
+/// being mistaken for a path separator.
+fn render_template(template: &str, resolve: impl Fn(&str) -> String, clean: impl Fn(String) -> String) -> String {
+    let mut out = String::with_capacity(template.len());
+    let mut rest = template;
+
+    while let Some(start) = rest.find('{') {
+        let Some(end) = rest[start..].find('}') else {
+            out.push_str(rest);
+            return out;
+        };
+        let end = start + end;
+
+        out.push_str(&rest[..start]);
+        out.push_str(&clean(resolve(&rest[start + 1..end])));
+        rest = &rest[end + 1..];
+    }
+
+    out.push_str(rest);
+    out
+}
+
+/// Resolves one `{placeholder}`/`{placeholder:0width}` token against track
+/// metadata.
+///
+/// Supported placeholders: `artist`, `album_artist`, `album`, `album_disc`,
+/// `title`, `track`, `disc`, `year`, `num_discs`.
+fn render_placeholder(token: &str, metadata: &TrackMetadata) -> String {
+    let (name, width) = match token.split_once(':') {
+        Some((name, spec)) => (name, parse_pad_width(spec)),
+        None => (token, None),
+    };
+
+    match name {
+        "artist" => artists_display(metadata.artists.iter().map(|artist| artist.name.clone()), 3),
+        "album_artist" => metadata.artists.first().map(|artist| artist.name.clone()).unwrap_or_default(),
+        "album" => metadata.album.name.clone(),
+        // Matches the old fixed ALBUM layout's disc handling: only called
+        // out when the album actually has more than one disc, so single-disc
+        // albums keep rendering as a plain album folder.
+        "album_disc" => if metadata.album.num_discs != 1 {
+            format!("{} (Disc {})", metadata.album.name, pad_numeric(metadata.disc_number, width))
+        } else {
+            metadata.album.name.clone()
+        },
+        "title" => metadata.track_name.clone(),
+        "track" => pad_numeric(metadata.number, width),
+        "disc" => pad_numeric(metadata.disc_number, width),
+        "num_discs" => pad_numeric(metadata.album.num_discs, width),
+        "year" => metadata.album.year.map(|year| pad_numeric(year, width)).unwrap_or_default(),
+        _ => {
+            tracing::warn!("Unknown template placeholder: {{{}}}", token);
+            format!("{{{}}}", token)
+        }
+    }
+}
+
+/// Parses the `0width` half of a `{placeholder:0width}` token, e.g. `"02"`
+/// yields a zero-pad width of `2`. Anything not starting with `0` is left
+/// unpadded.
+fn parse_pad_width(spec: &str) -> Option<usize> {
+    spec.strip_prefix('0')?.parse::<usize>().ok()
+}
+
+fn pad_numeric(value: impl std::fmt::Display, width: Option<usize>) -> String {
+    match width {
+        Some(width) => format!("{:0>width$}", value.to_string(), width = width),
+        None => value.to_string(),
+    }
+}
+
+/// Collapses long artist lists to the first `max` names plus "and others",
+/// the one piece of layout logic too bespoke to express as a placeholder.
+fn artists_display(names: impl ExactSizeIterator<Item = String>, max: usize) -> String {
+    if names.len() > max {
+        names
+            .take(max)
+            .chain(["and others".to_string()])
+            .collect::<Vec<_>>()
+            .join(", ")
+    } else {
+        names.collect::<Vec<_>>().join(", ")
+    }
 }
 
 fn write_link<P: AsRef<Path>, Q: AsRef<Path>>(original: P, link: Q) -> io::Result<()> {
@@ -292,3 +711,110 @@ fn write_link<P: AsRef<Path>, Q: AsRef<Path>>(original: P, link: Q) -> io::Resul
     }
     Ok(())
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn resample_stereo_same_rate_is_a_noop() {
+        let samples = vec![1, -1, 2, -2, 3, -3];
+        assert_eq!(resample_stereo(&samples, 44100, 44100), samples);
+    }
+
+    #[test]
+    fn resample_stereo_empty_input_is_empty_output() {
+        assert!(resample_stereo(&[], 44100, 22050).is_empty());
+    }
+
+    #[test]
+    fn resample_stereo_downsamples_to_the_expected_frame_count() {
+        let samples: Vec<i32> = (0..400).collect(); // 200 stereo frames
+        let out = resample_stereo(&samples, 44100, 22050);
+        assert_eq!(out.len() / 2, 200 * 22050 / 44100);
+    }
+
+    #[test]
+    fn resample_stereo_clamps_the_tail_instead_of_reading_out_of_bounds() {
+        // Upsampling 2x puts the fractional source position of the last
+        // output frames at or past the final source frame (2.0 and 2.5 for
+        // 3 source frames); this must clamp rather than index past the end
+        // of the buffer.
+        let samples = vec![10, -10, 20, -20, 30, -30];
+        let out = resample_stereo(&samples, 44100, 88200);
+        assert_eq!(out.len(), (3u64 * 88200 / 44100) as usize * 2);
+    }
+
+    #[test]
+    fn normalize_lowercases_and_strips_punctuation() {
+        assert_eq!(normalize("The Beatles!"), "the beatles");
+    }
+
+    #[test]
+    fn normalize_collapses_repeated_whitespace() {
+        assert_eq!(normalize("Abbey   Road"), "abbey road");
+    }
+
+    #[test]
+    fn parse_pad_width_reads_a_zero_prefixed_width() {
+        assert_eq!(parse_pad_width("02"), Some(2));
+    }
+
+    #[test]
+    fn parse_pad_width_ignores_specs_without_a_leading_zero() {
+        assert_eq!(parse_pad_width("2"), None);
+    }
+
+    #[test]
+    fn pad_numeric_zero_pads_to_width() {
+        assert_eq!(pad_numeric(7, Some(2)), "07");
+    }
+
+    #[test]
+    fn pad_numeric_leaves_value_unpadded_without_a_width() {
+        assert_eq!(pad_numeric(7, None), "7");
+    }
+
+    #[test]
+    fn artists_display_joins_all_names_under_the_limit() {
+        let names = vec!["A".to_string(), "B".to_string()];
+        assert_eq!(artists_display(names.into_iter(), 3), "A, B");
+    }
+
+    #[test]
+    fn artists_display_collapses_past_the_limit() {
+        let names = vec!["A".to_string(), "B".to_string(), "C".to_string(), "D".to_string()];
+        assert_eq!(artists_display(names.into_iter(), 2), "A, B, and others");
+    }
+
+    #[test]
+    fn render_template_substitutes_tokens() {
+        let out = render_template(
+            "{artist} - {title}",
+            |token| match token {
+                "artist" => "Tester".to_string(),
+                "title" => "Song".to_string(),
+                _ => String::new(),
+            },
+            |value| value,
+        );
+        assert_eq!(out, "Tester - Song");
+    }
+
+    #[test]
+    fn render_template_cleans_resolved_values_before_splicing_them_in() {
+        // A resolved value containing "/" must go through `clean` before
+        // it's spliced in, so it can't be mistaken for a literal path
+        // separator once it lands in the output.
+        let out = render_template(
+            "{artist}/{title}",
+            |token| match token {
+                "artist" => "AC/DC".to_string(),
+                "title" => "Song".to_string(),
+                _ => String::new(),
+            },
+            |value| value.replace('/', ""),
+        );
+        assert_eq!(out, "ACDC/Song");
+    }
+}